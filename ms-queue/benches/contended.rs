@@ -0,0 +1,185 @@
+//! Pushes and pops under heavy oversubscription (many more threads than
+//! cores), the regime where `Backoff` in the CAS retry loops is meant to
+//! pay off over a tight spin.
+//!
+//! `queue_contended_push_pop` measures the real `Queue`, whose retry loops
+//! use `Backoff::snooze` (escalating to a CPU yield under contention).
+//! `queue_contended_push_pop_tight_spin` measures a bench-only
+//! Michael-Scott queue with the same node linking that busy-spins on every
+//! retry. It has no cache padding on `head`/`tail` and no `Blocked`-node
+//! handling, so the gap between the two numbers includes those differences
+//! as well as the backoff.
+
+use std::mem::MaybeUninit;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use crossbeam_epoch::{pin, unprotected, Atomic, Owned, Shared};
+use crossbeam_utils::thread;
+use ms_queue::Queue;
+
+struct Node<T> {
+    data: MaybeUninit<T>,
+    next: Atomic<Node<T>>,
+}
+
+/// A non-blocking Michael-Scott queue shaped like the data-mode half of
+/// [`Queue`], but with plain (not cache-padded) `head`/`tail` and CAS retry
+/// loops that busy-spin instead of backing off. Serves as the tight-spin
+/// baseline for the `Backoff` in [`Queue`].
+struct TightSpinQueue<T> {
+    head: Atomic<Node<T>>,
+    tail: Atomic<Node<T>>,
+}
+
+impl<T> TightSpinQueue<T> {
+    fn new() -> Self {
+        let sentinel = Owned::new(Node {
+            data: MaybeUninit::uninit(),
+            next: Atomic::null(),
+        });
+
+        unsafe {
+            let guard = &unprotected();
+            let sentinel = sentinel.into_shared(guard);
+            Self {
+                head: Atomic::from(sentinel),
+                tail: Atomic::from(sentinel),
+            }
+        }
+    }
+
+    fn push(&self, t: T) {
+        let guard = &pin();
+        let new = Owned::new(Node {
+            data: MaybeUninit::new(t),
+            next: Atomic::null(),
+        });
+        let new = Owned::into_shared(new, guard);
+
+        loop {
+            let tail_snapshot = self.tail.load(Ordering::Acquire, guard);
+            let tail_ref = unsafe { tail_snapshot.deref() };
+            let next = tail_ref.next.load(Ordering::Acquire, guard);
+
+            if !next.is_null() {
+                let _ = self.tail.compare_exchange(
+                    tail_snapshot,
+                    next,
+                    Ordering::Release,
+                    Ordering::Relaxed,
+                    guard,
+                );
+                core::hint::spin_loop();
+                continue;
+            }
+
+            if tail_ref
+                .next
+                .compare_exchange(
+                    Shared::null(),
+                    new,
+                    Ordering::Release,
+                    Ordering::Relaxed,
+                    guard,
+                )
+                .is_ok()
+            {
+                let _ = self.tail.compare_exchange(
+                    tail_snapshot,
+                    new,
+                    Ordering::Release,
+                    Ordering::Relaxed,
+                    guard,
+                );
+                return;
+            }
+
+            core::hint::spin_loop();
+        }
+    }
+
+    fn try_pop(&self) -> Option<T> {
+        let guard = &pin();
+
+        loop {
+            let head_snapshot = self.head.load(Ordering::Acquire, guard);
+            let head = unsafe { head_snapshot.deref() };
+            let next = head.next.load(Ordering::Acquire, guard);
+
+            let next_node = unsafe { next.as_ref() }?;
+
+            if self
+                .head
+                .compare_exchange(
+                    head_snapshot,
+                    next,
+                    Ordering::Release,
+                    Ordering::Relaxed,
+                    guard,
+                )
+                .is_ok()
+            {
+                unsafe {
+                    guard.defer_destroy(head_snapshot);
+                    return Some(core::ptr::read(&next_node.data).assume_init());
+                }
+            }
+
+            core::hint::spin_loop();
+        }
+    }
+}
+
+impl<T> Drop for TightSpinQueue<T> {
+    fn drop(&mut self) {
+        while self.try_pop().is_some() {}
+    }
+}
+
+fn contended_push_pop(c: &mut Criterion) {
+    let threads = num_cpus::get() * 4;
+
+    c.bench_function("queue_contended_push_pop", |b| {
+        b.iter(|| {
+            let queue = Arc::new(Queue::new());
+
+            thread::scope(|scope| {
+                for _ in 0..threads {
+                    let queue = Arc::clone(&queue);
+                    scope.spawn(move |_| {
+                        let guard = &pin();
+                        for i in 0..1_000 {
+                            queue.push(i, guard);
+                            queue.try_pop(guard);
+                        }
+                    });
+                }
+            })
+            .unwrap();
+        });
+    });
+
+    c.bench_function("queue_contended_push_pop_tight_spin", |b| {
+        b.iter(|| {
+            let queue = Arc::new(TightSpinQueue::new());
+
+            thread::scope(|scope| {
+                for _ in 0..threads {
+                    let queue = Arc::clone(&queue);
+                    scope.spawn(move |_| {
+                        for i in 0..1_000 {
+                            queue.push(i);
+                            queue.try_pop();
+                        }
+                    });
+                }
+            })
+            .unwrap();
+        });
+    });
+}
+
+criterion_group!(benches, contended_push_pop);
+criterion_main!(benches);
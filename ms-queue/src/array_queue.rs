@@ -0,0 +1,321 @@
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{self, AtomicUsize, Ordering};
+
+use crossbeam_utils::{Backoff, CachePadded};
+
+struct Slot<T> {
+    stamp: AtomicUsize,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+/// A bounded, lock-free MPMC queue backed by a fixed-size ring buffer.
+///
+/// Unlike [`Queue`](crate::queue::Queue), this never allocates after
+/// construction and needs no epoch pinning, since no node is ever freed.
+pub struct ArrayQueue<T> {
+    buffer: Box<[Slot<T>]>,
+    capacity: usize,
+    one_lap: usize,
+    head: CachePadded<AtomicUsize>,
+    tail: CachePadded<AtomicUsize>,
+}
+
+unsafe impl<T: Send> Sync for ArrayQueue<T> {}
+unsafe impl<T: Send> Send for ArrayQueue<T> {}
+
+impl<T> core::fmt::Debug for ArrayQueue<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ArrayQueue")
+            .field("capacity", &self.capacity)
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+impl<T> ArrayQueue<T> {
+    /// Creates a new, empty queue with the given fixed `capacity`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "capacity must be non-zero");
+
+        let buffer: Box<[Slot<T>]> = (0..capacity)
+            .map(|i| Slot {
+                stamp: AtomicUsize::new(i),
+                value: UnsafeCell::new(MaybeUninit::uninit()),
+            })
+            .collect();
+
+        // The bits of head/tail below `one_lap` hold the slot index; the
+        // bits from `one_lap` upward count the laps through the ring buffer.
+        let one_lap = (capacity + 1).next_power_of_two();
+
+        Self {
+            buffer,
+            capacity,
+            one_lap,
+            head: CachePadded::new(AtomicUsize::new(0)),
+            tail: CachePadded::new(AtomicUsize::new(0)),
+        }
+    }
+
+    pub fn push(&self, value: T) -> Result<(), T> {
+        let backoff = Backoff::new();
+        let mut tail = self.tail.load(Ordering::Relaxed);
+
+        loop {
+            let index = tail & (self.one_lap - 1);
+            let lap = tail & !(self.one_lap - 1);
+
+            // Index never reaches `capacity`: the slot that fills the last
+            // spot in a lap jumps straight to index 0 of the next lap below,
+            // so there is no padding range to skip over.
+            let new_tail = if index + 1 < self.capacity {
+                tail + 1
+            } else {
+                lap.wrapping_add(self.one_lap)
+            };
+
+            let slot = &self.buffer[index];
+            let stamp = slot.stamp.load(Ordering::Acquire);
+
+            if stamp == tail {
+                match self.tail.compare_exchange_weak(
+                    tail,
+                    new_tail,
+                    Ordering::SeqCst,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        unsafe {
+                            slot.value.get().write(MaybeUninit::new(value));
+                        }
+                        slot.stamp.store(tail + 1, Ordering::Release);
+                        return Ok(());
+                    }
+                    Err(t) => {
+                        tail = t;
+                        backoff.snooze();
+                    }
+                }
+            } else if stamp.wrapping_add(self.one_lap) == tail + 1 {
+                // The slot's lap is one behind the tail's lap: this looks
+                // full, but a pop may have already claimed `head` here and
+                // just not finished restamping the slot yet. Only report
+                // full once `head` confirms there's truly no room; otherwise
+                // the in-flight pop will free the slot shortly.
+                atomic::fence(Ordering::SeqCst);
+                let head = self.head.load(Ordering::Relaxed);
+
+                if head.wrapping_add(self.one_lap) == tail {
+                    return Err(value);
+                }
+
+                backoff.snooze();
+                tail = self.tail.load(Ordering::Relaxed);
+            } else {
+                backoff.snooze();
+                tail = self.tail.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn pop(&self) -> Option<T> {
+        let backoff = Backoff::new();
+        let mut head = self.head.load(Ordering::Relaxed);
+
+        loop {
+            let index = head & (self.one_lap - 1);
+            let lap = head & !(self.one_lap - 1);
+
+            let new_head = if index + 1 < self.capacity {
+                head + 1
+            } else {
+                lap.wrapping_add(self.one_lap)
+            };
+
+            let slot = &self.buffer[index];
+            let stamp = slot.stamp.load(Ordering::Acquire);
+
+            if stamp == head + 1 {
+                match self.head.compare_exchange_weak(
+                    head,
+                    new_head,
+                    Ordering::SeqCst,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        let value = unsafe { slot.value.get().read().assume_init() };
+                        // Mark the slot ready for the next lap through it.
+                        slot.stamp
+                            .store(head.wrapping_add(self.one_lap), Ordering::Release);
+                        return Some(value);
+                    }
+                    Err(h) => {
+                        head = h;
+                        backoff.snooze();
+                    }
+                }
+            } else if stamp == head {
+                return None;
+            } else {
+                backoff.snooze();
+                head = self.head.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        loop {
+            let tail = self.tail.load(Ordering::SeqCst);
+            let head = self.head.load(Ordering::SeqCst);
+
+            if self.tail.load(Ordering::SeqCst) == tail {
+                let hix = head % self.one_lap;
+                let tix = tail % self.one_lap;
+
+                return if hix < tix {
+                    tix - hix
+                } else if hix > tix {
+                    self.capacity - hix + tix
+                } else if tail == head {
+                    0
+                } else {
+                    self.capacity
+                };
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len() == self.capacity
+    }
+}
+
+impl<T> Drop for ArrayQueue<T> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crossbeam_utils::thread;
+
+    #[test]
+    fn push_pop() {
+        let q = ArrayQueue::new(2);
+        assert!(q.is_empty());
+        assert_eq!(q.push(1), Ok(()));
+        assert_eq!(q.push(2), Ok(()));
+        assert!(q.is_full());
+        assert_eq!(q.push(3), Err(3));
+
+        assert_eq!(q.pop(), Some(1));
+        assert_eq!(q.pop(), Some(2));
+        assert_eq!(q.pop(), None);
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    fn wraps_around_many_laps() {
+        let q = ArrayQueue::new(3);
+
+        for lap in 0..100 {
+            for i in 0..3 {
+                assert_eq!(q.push(lap * 3 + i), Ok(()));
+            }
+            for i in 0..3 {
+                assert_eq!(q.pop(), Some(lap * 3 + i));
+            }
+        }
+    }
+
+    #[test]
+    fn push_never_spuriously_full_on_empty_queue() {
+        // Regression test: `push` must not report `Err` (full) while the
+        // queue is actually empty, even after many wraps around the ring.
+        let q = ArrayQueue::new(1);
+
+        for i in 0..1_000 {
+            assert_eq!(q.push(i), Ok(()));
+            assert_eq!(q.pop(), Some(i));
+        }
+    }
+
+    #[test]
+    fn single_slot_mpmc_never_spuriously_full() {
+        // A concurrent pop that has claimed `head` but not yet restamped the
+        // slot must not make a same-lap producer see the slot as full.
+        const COUNT: usize = 5_000;
+
+        let q: ArrayQueue<usize> = ArrayQueue::new(1);
+
+        thread::scope(|scope| {
+            scope.spawn(|_| {
+                for i in 0..COUNT {
+                    loop {
+                        if q.push(i).is_ok() {
+                            break;
+                        }
+                    }
+                }
+            });
+
+            scope.spawn(|_| {
+                let mut n = 0;
+                while n < COUNT {
+                    if q.pop().is_some() {
+                        n += 1;
+                    }
+                }
+            });
+        })
+        .unwrap();
+
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    fn mpmc() {
+        const COUNT: usize = 100_000;
+
+        let q: ArrayQueue<usize> = ArrayQueue::new(16);
+
+        thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|_| {
+                    for i in 0..COUNT / 4 {
+                        while q.push(i).is_err() {}
+                    }
+                });
+            }
+
+            for _ in 0..4 {
+                scope.spawn(|_| {
+                    let mut n = 0;
+                    while n < COUNT / 4 {
+                        if q.pop().is_some() {
+                            n += 1;
+                        }
+                    }
+                });
+            }
+        })
+        .unwrap();
+
+        assert!(q.is_empty());
+    }
+}
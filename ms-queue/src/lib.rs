@@ -0,0 +1,7 @@
+pub mod array_queue;
+pub mod queue;
+pub mod seg_queue;
+
+pub use array_queue::ArrayQueue;
+pub use queue::Queue;
+pub use seg_queue::SegQueue;
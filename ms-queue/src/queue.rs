@@ -1,13 +1,37 @@
+use core::cell::UnsafeCell;
 use core::mem::MaybeUninit;
-use core::ptr;
-use core::sync::atomic::Ordering;
+use core::sync::atomic::{AtomicU8, Ordering};
+use core::time::Duration;
+
+use std::thread::{self, Thread};
+use std::time::Instant;
 
 use crossbeam_epoch::{unprotected, Atomic, Guard, Owned, Shared};
-use crossbeam_utils::CachePadded;
+use crossbeam_utils::{Backoff, CachePadded};
+
+/// States for a `Blocked` node's request, transitioned by exactly one
+/// winning `compare_exchange` from whichever of a fulfilling `push` or a
+/// timing-out `pop_timeout` gets there first.
+const STATE_PENDING: u8 = 0;
+const STATE_FULFILLED: u8 = 1;
+const STATE_CANCELLED: u8 = 2;
+
+/// The payload of a node: either a value waiting to be popped, or a request
+/// left behind by a blocking `pop` that is waiting to be filled by a `push`.
+///
+/// The queue never holds both kinds of node at once: it is either a data
+/// queue or a (dual) request queue at any given moment.
+enum NodeData<T> {
+    Data(MaybeUninit<T>),
+    Blocked {
+        thread: Thread,
+        slot: UnsafeCell<MaybeUninit<T>>,
+        state: AtomicU8,
+    },
+}
 
-#[derive(Debug)]
 struct Node<T> {
-    data: MaybeUninit<T>,
+    data: NodeData<T>,
     next: Atomic<Node<T>>,
 }
 
@@ -28,7 +52,7 @@ impl<T> Default for Queue<T> {
         };
 
         let sentinel = Owned::new(Node {
-            data: MaybeUninit::uninit(),
+            data: NodeData::Data(MaybeUninit::uninit()),
             next: Atomic::null(),
         });
 
@@ -47,71 +71,411 @@ impl<T> Queue<T> {
         Self::default()
     }
 
+    /// Pushes a value, handing it straight to a parked consumer if one is
+    /// waiting at the front of the queue, or else enqueuing it as a new
+    /// `Data` node.
+    ///
+    /// The fulfill-or-enqueue decision is re-checked on every iteration
+    /// against the queue's current state, rather than decided once: a
+    /// consumer may park (turning the queue into request-mode) in the gap
+    /// between a failed fulfill attempt and an enqueue attempt, and an
+    /// enqueue that lands behind a `Blocked` node would both leave that
+    /// consumer parked forever and break the Data/Blocked exclusivity
+    /// invariant.
     pub fn push(&self, t: T, guard: &Guard) {
+        let mut t = t;
+
+        loop {
+            match self.try_fulfill(t, guard) {
+                None => return,
+                Some(returned) => t = returned,
+            }
+
+            match self.try_append_data(t, guard) {
+                Ok(()) => return,
+                Err(returned) => t = returned,
+            }
+        }
+    }
+
+    /// If a consumer is parked in `pop` waiting at the front of the queue,
+    /// hands `t` directly to it and wakes it up. Returns `None` once the
+    /// value has been delivered this way, or `Some(t)` if there was no
+    /// waiting consumer (or the one found had just been cancelled by a
+    /// timing-out `pop_timeout`) and the caller should fall back to a
+    /// normal enqueue.
+    fn try_fulfill(&self, t: T, guard: &Guard) -> Option<T> {
+        let backoff = Backoff::new();
+
+        loop {
+            let head_snapshot = self.head.load(Ordering::Acquire, guard);
+            let head_ref = unsafe { head_snapshot.deref() };
+            let next = head_ref.next.load(Ordering::Acquire, guard);
+
+            let next_ref = match unsafe { next.as_ref() } {
+                Some(next_ref) => next_ref,
+                None => return Some(t),
+            };
+
+            let (thread, slot, state) = match &next_ref.data {
+                NodeData::Data(_) => return Some(t),
+                NodeData::Blocked {
+                    thread,
+                    slot,
+                    state,
+                } => (thread, slot, state),
+            };
+
+            if state.load(Ordering::Acquire) == STATE_CANCELLED {
+                // Already abandoned by a timed-out consumer; unlink it and
+                // keep scanning rather than trying to fulfill it.
+                if self
+                    .head
+                    .compare_exchange(
+                        head_snapshot,
+                        next,
+                        Ordering::Release,
+                        Ordering::Relaxed,
+                        guard,
+                    )
+                    .is_ok()
+                {
+                    unsafe { guard.defer_destroy(head_snapshot) };
+                }
+                backoff.snooze();
+                continue;
+            }
+
+            let thread = thread.clone();
+
+            if self
+                .head
+                .compare_exchange(
+                    head_snapshot,
+                    next,
+                    Ordering::Release,
+                    Ordering::Relaxed,
+                    guard,
+                )
+                .is_ok()
+            {
+                // We now exclusively own this node (no other `push` can also
+                // win this CAS), so the only remaining race is against this
+                // same consumer timing out and cancelling concurrently.
+                unsafe {
+                    slot.get().write(MaybeUninit::new(t));
+                }
+
+                let fulfilled = state
+                    .compare_exchange(
+                        STATE_PENDING,
+                        STATE_FULFILLED,
+                        Ordering::AcqRel,
+                        Ordering::Acquire,
+                    )
+                    .is_ok();
+
+                unsafe { guard.defer_destroy(head_snapshot) };
+
+                if fulfilled {
+                    thread.unpark();
+                    return None;
+                }
+
+                // The consumer cancelled first and isn't reading the slot;
+                // reclaim the value instead of leaking it there.
+                let reclaimed = unsafe { ptr_read(slot.get() as *const MaybeUninit<T>) };
+                return Some(reclaimed);
+            }
+
+            backoff.snooze();
+        }
+    }
+
+    /// Appends a single `Data` node containing `t` at the tail. Returns
+    /// `Err(t)` without having enqueued anything if the front of the queue
+    /// (`head.next`) turns out to be a `Blocked` node, live or cancelled: that means the
+    /// queue is in request-mode, and the caller must retry fulfillment
+    /// instead of enqueuing data behind a waiting consumer.
+    ///
+    /// Note that a node already reachable *through* `head` can be a former
+    /// `Blocked` node repurposed as the new sentinel once consumed; its
+    /// `data` tag is then stale and irrelevant, which is why this checks
+    /// `head.next` rather than trusting the tail node's own `data` field.
+    fn try_append_data(&self, t: T, guard: &Guard) -> Result<(), T> {
         let new = Owned::new(Node {
-            data: MaybeUninit::new(t),
+            data: NodeData::Data(MaybeUninit::new(t)),
             next: Atomic::null(),
         });
-
         let new = Owned::into_shared(new, guard);
+        let backoff = Backoff::new();
 
         loop {
             let tail_snapshot = self.tail.load(Ordering::Acquire, guard);
-
             let tail_ref = unsafe { tail_snapshot.deref() };
             let next = tail_ref.next.load(Ordering::Acquire, guard);
 
             if !next.is_null() {
-                let _ = self
-                    .tail
-                    .compare_and_set(tail_snapshot, next, Ordering::Release, guard);
+                let _ = self.tail.compare_exchange(
+                    tail_snapshot,
+                    next,
+                    Ordering::Release,
+                    Ordering::Relaxed,
+                    guard,
+                );
+                backoff.snooze();
                 continue;
             }
 
+            let head_snapshot = self.head.load(Ordering::Acquire, guard);
+            let head_ref = unsafe { head_snapshot.deref() };
+            let head_next = head_ref.next.load(Ordering::Acquire, guard);
+            let is_request_mode =
+                matches!(unsafe { head_next.as_ref() }, Some(n) if matches!(n.data, NodeData::Blocked { .. }));
+
+            if is_request_mode {
+                // Safe: `new` was never linked into the queue, so nothing
+                // else can be observing it.
+                let new_ref = unsafe { new.deref() };
+                let NodeData::Data(data) = &new_ref.data else {
+                    unreachable!("just constructed a Data node")
+                };
+                let value = unsafe { ptr_read(data as *const MaybeUninit<T>) };
+                drop(unsafe { new.into_owned() });
+                return Err(value);
+            }
+
             if tail_ref
                 .next
-                .compare_and_set(Shared::null(), new, Ordering::Release, guard)
+                .compare_exchange(
+                    Shared::null(),
+                    new,
+                    Ordering::Release,
+                    Ordering::Relaxed,
+                    guard,
+                )
                 .is_ok()
             {
-                let _ = self
-                    .tail
-                    .compare_and_set(tail_snapshot, new, Ordering::Release, guard);
-                break;
+                let _ = self.tail.compare_exchange(
+                    tail_snapshot,
+                    new,
+                    Ordering::Release,
+                    Ordering::Relaxed,
+                    guard,
+                );
+                return Ok(());
             }
+
+            backoff.snooze();
         }
     }
 
     pub fn try_pop(&self, guard: &Guard) -> Option<T> {
+        let backoff = Backoff::new();
+
         loop {
             let head_snapshot = self.head.load(Ordering::Acquire, guard);
             let head = unsafe { head_snapshot.deref() };
             let next = head.next.load(Ordering::Acquire, guard);
 
-            if let Some(next_node) = unsafe { next.as_ref() } {
-                let tail_snapshot = self.tail.load(Ordering::Relaxed, guard);
-                if tail_snapshot == head_snapshot {
-                    let _ =
-                        self.tail
-                            .compare_and_set(tail_snapshot, next, Ordering::Release, guard);
+            let next_node = unsafe { next.as_ref() }?;
+
+            // A leading `Blocked` node means the data side is empty: other
+            // consumers are already queued up waiting for a `push`.
+            if matches!(next_node.data, NodeData::Blocked { .. }) {
+                return None;
+            }
+
+            let tail_snapshot = self.tail.load(Ordering::Relaxed, guard);
+            if tail_snapshot == head_snapshot {
+                let _ = self.tail.compare_exchange(
+                    tail_snapshot,
+                    next,
+                    Ordering::Release,
+                    Ordering::Relaxed,
+                    guard,
+                );
+            }
+
+            if self
+                .head
+                .compare_exchange(
+                    head_snapshot,
+                    next,
+                    Ordering::Release,
+                    Ordering::Relaxed,
+                    guard,
+                )
+                .is_ok()
+            {
+                unsafe {
+                    guard.defer_destroy(head_snapshot);
+                    let NodeData::Data(data) = &next_node.data else {
+                        unreachable!("leading Blocked node was ruled out above")
+                    };
+                    return Some(ptr_read(data as *const MaybeUninit<T>));
                 }
+            }
 
-                if self
-                    .head
-                    .compare_and_set(head_snapshot, next, Ordering::Release, guard)
-                    .is_ok()
-                {
-                    unsafe {
-                        guard.defer_destroy(head_snapshot);
-                        return Some(ptr::read(&next_node.data).assume_init());
+            backoff.snooze();
+        }
+    }
+
+    /// Pops a value, parking the current thread until one is pushed if the
+    /// queue is currently empty.
+    pub fn pop(&self, guard: &Guard) -> T {
+        loop {
+            if let Some(t) = self.try_pop(guard) {
+                return t;
+            }
+
+            match self.park_for_value(guard, None) {
+                Some(t) => return t,
+                None => continue,
+            }
+        }
+    }
+
+    /// Like [`pop`](Self::pop), but gives up and returns `None` if no value
+    /// arrives within `dur`.
+    pub fn pop_timeout(&self, dur: Duration, guard: &Guard) -> Option<T> {
+        if let Some(t) = self.try_pop(guard) {
+            return Some(t);
+        }
+
+        self.park_for_value(guard, Some(dur))
+    }
+
+    /// Enqueues a `Blocked` request node and parks until it is fulfilled (or,
+    /// if `timeout` is `Some`, until it elapses). Returns `None` only when a
+    /// timeout elapsed with no value delivered.
+    ///
+    /// Symmetric to `try_append_data`'s request-mode bail-out: a `Data` node
+    /// can land at the front of the queue in the gap between the caller's
+    /// failed `try_pop` and this append, so every iteration re-checks
+    /// `head.next` and, if it finds one, pops it directly instead of linking
+    /// our `Blocked` node behind it. Skipping this check would let a `Data`
+    /// and a `Blocked` node coexist, and with `timeout: None` this thread
+    /// would then park forever behind data nobody will ever hand to it.
+    fn park_for_value(&self, guard: &Guard, timeout: Option<Duration>) -> Option<T> {
+        let slot = UnsafeCell::new(MaybeUninit::uninit());
+        let state = AtomicU8::new(STATE_PENDING);
+
+        let new = Owned::new(Node {
+            data: NodeData::Blocked {
+                thread: thread::current(),
+                slot,
+                state,
+            },
+            next: Atomic::null(),
+        });
+        let new = Owned::into_shared(new, guard);
+        let backoff = Backoff::new();
+
+        loop {
+            let head_snapshot = self.head.load(Ordering::Acquire, guard);
+            let head_ref = unsafe { head_snapshot.deref() };
+            let head_next = head_ref.next.load(Ordering::Acquire, guard);
+            let is_data_mode =
+                matches!(unsafe { head_next.as_ref() }, Some(n) if matches!(n.data, NodeData::Data(_)));
+
+            if is_data_mode {
+                if let Some(t) = self.try_pop(guard) {
+                    // Safe: `new` was never linked into the queue, so
+                    // nothing else can be observing it.
+                    drop(unsafe { new.into_owned() });
+                    return Some(t);
+                }
+                backoff.snooze();
+                continue;
+            }
+
+            let tail_snapshot = self.tail.load(Ordering::Acquire, guard);
+            let tail_ref = unsafe { tail_snapshot.deref() };
+            let next = tail_ref.next.load(Ordering::Acquire, guard);
+
+            if !next.is_null() {
+                let _ = self.tail.compare_exchange(
+                    tail_snapshot,
+                    next,
+                    Ordering::Release,
+                    Ordering::Relaxed,
+                    guard,
+                );
+                backoff.snooze();
+                continue;
+            }
+
+            if tail_ref
+                .next
+                .compare_exchange(
+                    Shared::null(),
+                    new,
+                    Ordering::Release,
+                    Ordering::Relaxed,
+                    guard,
+                )
+                .is_ok()
+            {
+                let _ = self.tail.compare_exchange(
+                    tail_snapshot,
+                    new,
+                    Ordering::Release,
+                    Ordering::Relaxed,
+                    guard,
+                );
+                break;
+            }
+
+            backoff.snooze();
+        }
+
+        let new_ref = unsafe { new.deref() };
+        let (state, slot) = match &new_ref.data {
+            NodeData::Blocked { state, slot, .. } => (state, slot),
+            NodeData::Data(_) => unreachable!("just constructed a Blocked node"),
+        };
+
+        let deadline = timeout.map(|dur| Instant::now() + dur);
+
+        loop {
+            if state.load(Ordering::Acquire) == STATE_FULFILLED {
+                return Some(unsafe { ptr_read(slot.get() as *const MaybeUninit<T>) });
+            }
+
+            match deadline {
+                None => thread::park(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        // Try to withdraw our request. If a push beat us to
+                        // it, `state` is already `STATE_FULFILLED` and the
+                        // value it left us is ours to take instead.
+                        if state
+                            .compare_exchange(
+                                STATE_PENDING,
+                                STATE_CANCELLED,
+                                Ordering::AcqRel,
+                                Ordering::Acquire,
+                            )
+                            .is_ok()
+                        {
+                            return None;
+                        }
+
+                        return Some(unsafe { ptr_read(slot.get() as *const MaybeUninit<T>) });
                     }
+                    thread::park_timeout(deadline - now);
                 }
-            } else {
-                return None;
             }
         }
     }
 }
 
+unsafe fn ptr_read<T>(data: *const MaybeUninit<T>) -> T {
+    unsafe { core::ptr::read(data).assume_init() }
+}
+
 impl<T> Drop for Queue<T> {
     fn drop(&mut self) {
         unsafe {
@@ -119,8 +483,24 @@ impl<T> Drop for Queue<T> {
 
             while self.try_pop(guard).is_some() {}
 
-            let sentinel = self.head.load(Ordering::Relaxed, guard);
-            drop(sentinel.into_owned());
+            // A thread parked in `pop`/`pop_timeout` borrows the queue, so
+            // any Blocked nodes left behind here are requests cancelled by a
+            // timed-out `pop_timeout`; free them without waking anyone.
+            loop {
+                let head_snapshot = self.head.load(Ordering::Relaxed, guard);
+                let head_ref = head_snapshot.deref();
+                let next = head_ref.next.load(Ordering::Relaxed, guard);
+
+                drop(head_snapshot.into_owned());
+
+                match next.as_ref() {
+                    Some(_) => self.head.store(next, Ordering::Relaxed),
+                    None => {
+                        self.head.store(Shared::null(), Ordering::Relaxed);
+                        break;
+                    }
+                }
+            }
         }
     }
 }
@@ -158,15 +538,6 @@ mod test {
             let gurad = &pin();
             self.queue.try_pop(gurad)
         }
-
-        pub fn pop(&self) -> T {
-            loop {
-                match self.try_pop() {
-                    None => continue,
-                    Some(t) => return t,
-                }
-            }
-        }
     }
 
     const CONC_COUNT: i64 = 1000000;
@@ -277,4 +648,137 @@ mod test {
         })
         .unwrap();
     }
+
+    #[test]
+    fn blocking_pop_waits_for_push() {
+        let queue: super::Queue<i64> = super::Queue::new();
+
+        thread::scope(|scope| {
+            scope.spawn(|_| {
+                let guard = &pin();
+                assert_eq!(queue.pop(guard), 7);
+            });
+
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            let guard = &pin();
+            queue.push(7, guard);
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn pop_timeout_expires_when_empty() {
+        let queue: super::Queue<i64> = super::Queue::new();
+        let guard = &pin();
+        assert_eq!(
+            queue.pop_timeout(std::time::Duration::from_millis(20), guard),
+            None
+        );
+    }
+
+    #[test]
+    fn blocking_pop_many_parked_consumers_all_wake() {
+        // Regression test for a lost-wakeup race: with several consumers
+        // parked at once, a concurrent burst of pushes must fulfill every
+        // one of them rather than leaving some stuck behind a Data node
+        // appended after they parked.
+        const N: i64 = 64;
+
+        let queue: super::Queue<i64> = super::Queue::new();
+        let received: std::sync::Mutex<Vec<i64>> = std::sync::Mutex::new(Vec::new());
+
+        thread::scope(|scope| {
+            for _ in 0..N {
+                scope.spawn(|_| {
+                    let guard = &pin();
+                    let v = queue.pop(guard);
+                    received.lock().unwrap().push(v);
+                });
+            }
+
+            // Give every consumer a chance to park before any value exists.
+            std::thread::sleep(std::time::Duration::from_millis(50));
+
+            thread::scope(|scope| {
+                for i in 0..N {
+                    let queue = &queue;
+                    scope.spawn(move |_| {
+                        let guard = &pin();
+                        queue.push(i, guard);
+                    });
+                }
+            })
+            .unwrap();
+        })
+        .unwrap();
+
+        let mut got = received.into_inner().unwrap();
+        got.sort_unstable();
+        assert_eq!(got, (0..N).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn pop_timeout_races_push_without_losing_values() {
+        // Regression test for a cancellation race: a `pop_timeout` whose
+        // deadline expires right as a `push` is fulfilling it must not let
+        // the pushed value vanish. Either the pop wins and returns it, or
+        // the cancellation wins and the value must still show up in a
+        // later `pop`.
+        const ROUNDS: i64 = 2_000;
+
+        let queue: super::Queue<i64> = super::Queue::new();
+
+        for i in 0..ROUNDS {
+            let popped = thread::scope(|scope| {
+                let handle = scope.spawn(|_| {
+                    let guard = &pin();
+                    queue.pop_timeout(Duration::from_micros(1), guard)
+                });
+
+                let guard = &pin();
+                queue.push(i, guard);
+
+                handle.join().unwrap()
+            })
+            .unwrap();
+
+            let value = match popped {
+                Some(v) => v,
+                None => {
+                    let guard = &pin();
+                    queue.pop(guard)
+                }
+            };
+            assert_eq!(value, i);
+        }
+    }
+
+    #[test]
+    fn blocking_pop_races_push_without_pre_parking() {
+        // Regression test for an asymmetric-invariant bug: unlike
+        // `blocking_pop_many_parked_consumers_all_wake`, this gives the
+        // consumer no head start, so a push can land a `Data` node before
+        // the consumer's blocking `pop` appends its `Blocked` node. If
+        // `park_for_value` doesn't re-check for that `Data` node before
+        // linking its own, the two coexist and the consumer (blocking with
+        // `timeout: None`) parks forever.
+        const ROUNDS: i64 = 2_000;
+
+        let queue: super::Queue<i64> = super::Queue::new();
+
+        for i in 0..ROUNDS {
+            thread::scope(|scope| {
+                let handle = scope.spawn(|_| {
+                    let guard = &pin();
+                    queue.pop(guard)
+                });
+
+                let guard = &pin();
+                queue.push(i, guard);
+
+                assert_eq!(handle.join().unwrap(), i);
+            })
+            .unwrap();
+        }
+    }
 }
@@ -0,0 +1,303 @@
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use crossbeam_epoch::{self as epoch, Atomic, Guard, Owned, Shared};
+use crossbeam_utils::{Backoff, CachePadded};
+
+const BLOCK_SIZE: usize = 32;
+
+struct Slot<T> {
+    value: UnsafeCell<MaybeUninit<T>>,
+    ready: AtomicBool,
+}
+
+// Aligned to a full cache line so the low 6 bits of a `Block` pointer are
+// always zero, leaving room to tag it with a slot index up to `BLOCK_SIZE`.
+#[repr(align(64))]
+struct Block<T> {
+    slots: [Slot<T>; BLOCK_SIZE],
+    next: Atomic<Block<T>>,
+}
+
+impl<T> Block<T> {
+    fn new() -> Owned<Self> {
+        // `Slot` has no safe default, so the array is built up manually
+        // rather than relying on `[Slot::new(); BLOCK_SIZE]`.
+        Owned::new(Self {
+            slots: [(); BLOCK_SIZE].map(|_| Slot {
+                value: UnsafeCell::new(MaybeUninit::uninit()),
+                ready: AtomicBool::new(false),
+            }),
+            next: Atomic::null(),
+        })
+    }
+}
+
+/// An unbounded MPMC queue that links fixed-size blocks of slots instead of
+/// single-element nodes, amortizing allocation over `BLOCK_SIZE` pushes.
+///
+/// The cursor for each end is a single `Atomic<Block<T>>` whose pointer tag
+/// doubles as the next slot index to claim within that block. Packing the
+/// block identity and the index into one word means a single CAS claims
+/// both atomically — a producer can never claim an index against a block
+/// other than the one it observed.
+#[derive(Debug)]
+pub struct SegQueue<T> {
+    head: CachePadded<Atomic<Block<T>>>,
+    tail: CachePadded<Atomic<Block<T>>>,
+    len: AtomicUsize,
+}
+
+unsafe impl<T: Send> Sync for SegQueue<T> {}
+unsafe impl<T: Send> Send for SegQueue<T> {}
+
+impl<T> Default for SegQueue<T> {
+    fn default() -> Self {
+        let guard = &epoch::pin();
+        let block = Block::new().into_shared(guard).with_tag(0);
+
+        Self {
+            head: CachePadded::new(Atomic::from(block)),
+            tail: CachePadded::new(Atomic::from(block)),
+            len: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl<T> SegQueue<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&self, value: T) {
+        let guard = &epoch::pin();
+        let backoff = Backoff::new();
+
+        loop {
+            let tail = self.tail.load(Ordering::Acquire, guard);
+            let index = tail.tag();
+
+            if index >= BLOCK_SIZE {
+                // Another producer claimed the last slot and is still
+                // linking/publishing the next block; wait for it.
+                backoff.snooze();
+                continue;
+            }
+
+            let block = unsafe { tail.deref() };
+            let claimed = self.tail.compare_exchange_weak(
+                tail,
+                tail.with_tag(index + 1),
+                Ordering::AcqRel,
+                Ordering::Relaxed,
+                guard,
+            );
+
+            match claimed {
+                Ok(_) => {
+                    let slot = &block.slots[index];
+                    unsafe {
+                        slot.value.get().write(MaybeUninit::new(value));
+                    }
+                    slot.ready.store(true, Ordering::Release);
+
+                    if index == BLOCK_SIZE - 1 {
+                        let next = Block::new().into_shared(guard).with_tag(0);
+                        block.next.store(next, Ordering::Release);
+                        self.tail.store(next, Ordering::Release);
+                    }
+
+                    self.len.fetch_add(1, Ordering::AcqRel);
+                    return;
+                }
+                Err(_) => backoff.snooze(),
+            }
+        }
+    }
+
+    pub fn try_pop(&self) -> Option<T> {
+        let guard = &epoch::pin();
+        let backoff = Backoff::new();
+
+        loop {
+            let head = self.head.load(Ordering::Acquire, guard);
+            let index = head.tag();
+
+            if index >= BLOCK_SIZE {
+                backoff.snooze();
+                continue;
+            }
+
+            if index >= self.tail_index_hint(head, guard) {
+                return None;
+            }
+
+            let block = unsafe { head.deref() };
+            let claimed = self.head.compare_exchange_weak(
+                head,
+                head.with_tag(index + 1),
+                Ordering::AcqRel,
+                Ordering::Relaxed,
+                guard,
+            );
+
+            if claimed.is_err() {
+                backoff.snooze();
+                continue;
+            }
+
+            let slot = &block.slots[index];
+            let slot_backoff = Backoff::new();
+            while !slot.ready.load(Ordering::Acquire) {
+                slot_backoff.snooze();
+            }
+
+            let value = unsafe { slot.value.get().read().assume_init() };
+            self.len.fetch_sub(1, Ordering::AcqRel);
+
+            if index == BLOCK_SIZE - 1 {
+                let next = self.wait_for_next(block, guard);
+                self.head.store(next.with_tag(0), Ordering::Release);
+                unsafe { guard.defer_destroy(head) };
+            }
+
+            return Some(value);
+        }
+    }
+
+    /// Returns the slot count claimable in `head_block`'s position relative
+    /// to the tail: how many of `head_block`'s slots have actually been
+    /// reserved by a `push`. If the tail is already in a later block, the
+    /// whole of `head_block` has been claimed.
+    fn tail_index_hint(&self, head_block: Shared<'_, Block<T>>, guard: &Guard) -> usize {
+        let tail = self.tail.load(Ordering::Acquire, guard);
+        if tail.as_raw() == head_block.as_raw() {
+            tail.tag().min(BLOCK_SIZE)
+        } else {
+            BLOCK_SIZE
+        }
+    }
+
+    fn wait_for_next<'g>(&self, block: &Block<T>, guard: &'g Guard) -> Shared<'g, Block<T>> {
+        let backoff = Backoff::new();
+        loop {
+            let next = block.next.load(Ordering::Acquire, guard);
+            if !next.is_null() {
+                return next;
+            }
+            backoff.snooze();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Acquire)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Drop for SegQueue<T> {
+    fn drop(&mut self) {
+        while self.try_pop().is_some() {}
+
+        unsafe {
+            let guard = epoch::unprotected();
+            let block = self.head.load(Ordering::Relaxed, guard);
+            if !block.is_null() {
+                drop(block.into_owned());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crossbeam_utils::thread;
+
+    #[test]
+    fn push_try_pop() {
+        let q: SegQueue<i64> = SegQueue::new();
+        assert!(q.is_empty());
+        q.push(39);
+        assert!(!q.is_empty());
+        assert_eq!(q.try_pop(), Some(39));
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    fn push_try_pop_across_blocks() {
+        let q: SegQueue<i64> = SegQueue::new();
+
+        for i in 0..(BLOCK_SIZE as i64) * 3 {
+            q.push(i);
+        }
+        for i in 0..(BLOCK_SIZE as i64) * 3 {
+            assert_eq!(q.try_pop(), Some(i));
+        }
+        assert_eq!(q.try_pop(), None);
+    }
+
+    #[test]
+    fn push_try_pop_many_spsc() {
+        const CONC_COUNT: i64 = 1_000_000;
+
+        let q: SegQueue<i64> = SegQueue::new();
+        assert!(q.is_empty());
+
+        thread::scope(|scope| {
+            scope.spawn(|_| {
+                let mut next = 0;
+
+                while next < CONC_COUNT {
+                    if let Some(elem) = q.try_pop() {
+                        assert_eq!(next, elem);
+                        next += 1;
+                    }
+                }
+            });
+
+            for i in 0..CONC_COUNT {
+                q.push(i);
+            }
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn push_try_pop_mpmc_across_many_blocks() {
+        const PER_PRODUCER: i64 = 20_000;
+        const PRODUCERS: i64 = 4;
+        const TOTAL: i64 = PER_PRODUCER * PRODUCERS;
+
+        let q: SegQueue<i64> = SegQueue::new();
+        let consumed = core::sync::atomic::AtomicI64::new(0);
+
+        thread::scope(|scope| {
+            for p in 0..PRODUCERS {
+                let q = &q;
+                scope.spawn(move |_| {
+                    for i in 0..PER_PRODUCER {
+                        q.push(p * PER_PRODUCER + i);
+                    }
+                });
+            }
+
+            for _ in 0..3 {
+                scope.spawn(|_| {
+                    while consumed.load(Ordering::Relaxed) < TOTAL {
+                        if q.try_pop().is_some() {
+                            consumed.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                });
+            }
+        })
+        .unwrap();
+
+        assert!(q.is_empty());
+    }
+}
@@ -0,0 +1,150 @@
+//! Pushes and pops under heavy oversubscription (many more threads than
+//! cores), the regime where `Backoff` in the CAS retry loops is meant to
+//! pay off over a tight spin.
+//!
+//! `stack_contended_push_pop` measures the real `Stack`, whose retry loops
+//! use `Backoff::snooze` (escalating to a CPU yield under contention).
+//! `stack_contended_push_pop_tight_spin` measures a bench-only stack that is
+//! otherwise identical but busy-spins on every retry, so the two numbers
+//! show what the escalating backoff actually buys under oversubscription.
+
+use std::mem::ManuallyDrop;
+use std::ptr;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use crossbeam_epoch::{Atomic, Owned};
+use crossbeam_utils::thread;
+use treiber_stack::Stack;
+
+struct Node<T> {
+    data: ManuallyDrop<T>,
+    next: Atomic<Node<T>>,
+}
+
+/// A Treiber stack identical to [`Stack`] except that its CAS retry loops
+/// busy-spin instead of backing off, serving as the "current tight spin"
+/// baseline the request's `Backoff` work is meant to improve on.
+struct TightSpinStack<T> {
+    head: Atomic<Node<T>>,
+}
+
+impl<T> TightSpinStack<T> {
+    fn new() -> Self {
+        Self {
+            head: Atomic::null(),
+        }
+    }
+
+    fn push(&self, t: T) {
+        let mut new_node = Owned::new(Node {
+            data: ManuallyDrop::new(t),
+            next: Atomic::null(),
+        });
+
+        let guard = crossbeam_epoch::pin();
+
+        loop {
+            let head_snapshot = self.head.load(Ordering::Acquire, &guard);
+            new_node.next.store(head_snapshot, Ordering::Relaxed);
+
+            match self.head.compare_exchange(
+                head_snapshot,
+                new_node,
+                Ordering::Release,
+                Ordering::Relaxed,
+                &guard,
+            ) {
+                Ok(_) => break,
+                Err(e) => {
+                    new_node = e.new;
+                    core::hint::spin_loop();
+                }
+            }
+        }
+    }
+
+    fn try_pop(&self) -> Option<T> {
+        let guard = crossbeam_epoch::pin();
+
+        loop {
+            let head_snapshot = self.head.load(Ordering::Acquire, &guard);
+            unsafe {
+                match head_snapshot.as_ref() {
+                    Some(head) => {
+                        let next = head.next.load(Ordering::Acquire, &guard);
+                        if self
+                            .head
+                            .compare_exchange(
+                                head_snapshot,
+                                next,
+                                Ordering::Release,
+                                Ordering::Relaxed,
+                                &guard,
+                            )
+                            .is_ok()
+                        {
+                            guard.defer_destroy(head_snapshot);
+                            let value = ManuallyDrop::into_inner(ptr::read(&head.data));
+                            return Some(value);
+                        }
+                        core::hint::spin_loop();
+                    }
+                    None => return None,
+                }
+            }
+        }
+    }
+}
+
+impl<T> Drop for TightSpinStack<T> {
+    fn drop(&mut self) {
+        while self.try_pop().is_some() {}
+    }
+}
+
+fn contended_push_pop(c: &mut Criterion) {
+    let threads = num_cpus::get() * 4;
+
+    c.bench_function("stack_contended_push_pop", |b| {
+        b.iter(|| {
+            let stack = Arc::new(Stack::new());
+
+            thread::scope(|scope| {
+                for _ in 0..threads {
+                    let stack = Arc::clone(&stack);
+                    scope.spawn(move |_| {
+                        for i in 0..1_000 {
+                            stack.push(i);
+                            stack.try_pop();
+                        }
+                    });
+                }
+            })
+            .unwrap();
+        });
+    });
+
+    c.bench_function("stack_contended_push_pop_tight_spin", |b| {
+        b.iter(|| {
+            let stack = Arc::new(TightSpinStack::new());
+
+            thread::scope(|scope| {
+                for _ in 0..threads {
+                    let stack = Arc::clone(&stack);
+                    scope.spawn(move |_| {
+                        for i in 0..1_000 {
+                            stack.push(i);
+                            stack.try_pop();
+                        }
+                    });
+                }
+            })
+            .unwrap();
+        });
+    });
+}
+
+criterion_group!(benches, contended_push_pop);
+criterion_main!(benches);
@@ -0,0 +1,3 @@
+pub mod stack;
+
+pub use stack::Stack;